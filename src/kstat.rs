@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use thiserror::Error;
 
 type Result<T> = std::result::Result<T, KstatError>;
@@ -19,18 +20,67 @@ pub enum KstatError {
 // There is presently no static CStr constructor, so use these constants with
 // the c() wrapper below:
 const MODULE_CAPS: &[u8] = b"caps\0";
+const MODULE_CPU_INFO: &[u8] = b"cpu_info\0";
+const MODULE_MEMORY_CAP: &[u8] = b"memory_cap\0";
 const MODULE_UNIX: &[u8] = b"unix\0";
 
 const NAME_SYSTEM_MISC: &[u8] = b"system_misc\0";
+const NAME_SYSTEM_PAGES: &[u8] = b"system_pages\0";
 
 const STAT_VALUE: &[u8] = b"value\0";
 const STAT_NCPUS: &[u8] = b"ncpus\0";
+const STAT_PHYSCAP: &[u8] = b"physcap\0";
+const STAT_SWAPCAP: &[u8] = b"swapcap\0";
+const STAT_RSS: &[u8] = b"rss\0";
+const STAT_SWAP: &[u8] = b"swap\0";
+const STAT_PHYSMEM: &[u8] = b"physmem\0";
+const STAT_FREEMEM: &[u8] = b"freemem\0";
+const STAT_CLOCK_MHZ: &[u8] = b"clock_MHz\0";
+const STAT_STATE: &[u8] = b"state\0";
+const STAT_BOOT_TIME: &[u8] = b"boot_time\0";
+
+// A cap value of 0 or this sentinel both mean "uncapped" in illumos's rctl
+// accounting.
+const CAP_UNLIMITED: u64 = std::os::raw::c_ulong::MAX;
 
 fn c(buf: &[u8]) -> &std::ffi::CStr {
     std::ffi::CStr::from_bytes_with_nul(buf).expect("invalid string constant")
 }
 
+/// A named kstat value, decoded according to its reported `data_type` so
+/// callers don't have to guess the width or sign of an arbitrary statistic.
+#[derive(Debug, Clone, PartialEq)]
+pub enum KstatNamedValue {
+    U32(u64),
+    Int32(i32),
+    Int64(i64),
+    UInt64(u64),
+    String(String),
+}
+
+fn normalize_cap(value: u64) -> Option<u64> {
+    if value == 0 || value == CAP_UNLIMITED {
+        None
+    } else {
+        Some(value)
+    }
+}
+
+/// sysconf(3C) name for the system page size; not exposed by any existing
+/// binding in this crate, so declare it by hand like the rest of the
+/// kstat(3KSTAT) surface.
+const _SC_PAGESIZE: std::os::raw::c_int = 11;
+
+extern "C" {
+    fn sysconf(name: std::os::raw::c_int) -> std::os::raw::c_long;
+}
+
+fn page_size() -> u64 {
+    unsafe { sysconf(_SC_PAGESIZE) as u64 }
+}
+
 mod wrapper {
+    use std::collections::HashMap;
     use std::ffi::CStr;
     use std::os::raw::c_char;
     use std::os::raw::c_int;
@@ -46,6 +96,15 @@ mod wrapper {
 
     const KSTAT_STRLEN: usize = 31;
 
+    // Values of KstatNamed::data_type, from <sys/kstat.h>.
+    const KSTAT_DATA_CHAR: c_uchar = 0;
+    const KSTAT_DATA_INT32: c_uchar = 1;
+    const KSTAT_DATA_UINT32: c_uchar = 2;
+    const KSTAT_DATA_INT64: c_uchar = 3;
+    const KSTAT_DATA_UINT64: c_uchar = 4;
+    const KSTAT_DATA_LONG: c_uchar = 5;
+    const KSTAT_DATA_ULONG: c_uchar = 6;
+
     #[repr(C)]
     struct Kstat {
         ks_crtime: c_longlong,
@@ -85,9 +144,12 @@ mod wrapper {
     #[derive(Copy, Clone)]
     union KstatValue {
         c: [c_char; 16],
+        i32: i32,
+        ui32: u32,
+        i64: i64,
+        ui64: u64,
         l: c_long,
         ul: c_ulong,
-        ui32: u32,
     }
 
     #[repr(C)]
@@ -179,6 +241,13 @@ mod wrapper {
             unsafe { ks.as_ref() }.name()
         }
 
+        /// Return the instance number of the current kstat.  This routine
+        /// will panic if step() has not returned true.
+        pub fn instance(&self) -> i32 {
+            let ks = self.ks.as_ref().expect("step() must return true first");
+            unsafe { ks.as_ref() }.ks_instance
+        }
+
         /// Look up a named kstat value.  For internal use by typed accessors.
         fn data_value(&self, statistic: &CStr) -> Option<NonNull<KstatNamed>> {
             let (ks, ksp) = if let Some(ks) = &self.ks {
@@ -211,6 +280,87 @@ mod wrapper {
             self.data_value(statistic)
                 .map(|kn| unsafe { kn.as_ref().value.ul } as u64)
         }
+
+        /// Look up a named kstat value and interpret it as a NUL-terminated
+        /// char array, lossily decoding it into an owned `String`.
+        pub fn data_string(&self, statistic: &CStr) -> Option<String> {
+            self.data_value(statistic)
+                .map(|kn| data_to_string(unsafe { &kn.as_ref().value.c }))
+        }
+
+        /// Look up a named kstat value and interpret it as a signed 64-bit
+        /// integer, widening whatever integer type it was actually stored
+        /// as.
+        pub fn data_i64(&self, statistic: &CStr) -> Option<i64> {
+            match self.data_value_typed(statistic)? {
+                super::KstatNamedValue::Int64(v) => Some(v),
+                super::KstatNamedValue::UInt64(v) => Some(v as i64),
+                super::KstatNamedValue::Int32(v) => Some(v as i64),
+                super::KstatNamedValue::U32(v) => Some(v as i64),
+                super::KstatNamedValue::String(_) => None,
+            }
+        }
+
+        /// Look up a named kstat value and decode it according to its
+        /// reported `data_type`, so callers don't have to guess the width.
+        pub fn data_value_typed(&self, statistic: &CStr) -> Option<super::KstatNamedValue> {
+            self.data_value(statistic)
+                .map(|kn| unsafe { named_value(kn.as_ref()) })
+        }
+
+        /// Read every named statistic of the current kstat into a map, each
+        /// decoded by its reported `data_type`.  This routine will panic if
+        /// step() has not returned true.
+        pub fn snapshot(&self) -> Option<HashMap<String, super::KstatNamedValue>> {
+            let ks = self.ks.as_ref().expect("step() must return true first");
+            let ksp = ks.as_ptr();
+            let ks = unsafe { ks.as_ref() };
+
+            if unsafe { kstat_read(self.kc.as_ptr(), ksp, null_mut()) } == -1 {
+                return None;
+            }
+
+            if ks.ks_type != KSTAT_TYPE_NAMED || ks.ks_data.is_null() {
+                return None;
+            }
+
+            let data = ks.ks_data as *const KstatNamed;
+            let mut stats = HashMap::with_capacity(ks.ks_ndata as usize);
+            for i in 0..ks.ks_ndata as isize {
+                let kn = unsafe { &*data.offset(i) };
+                let name = unsafe { CStr::from_ptr(kn.name.as_ptr()) }
+                    .to_string_lossy()
+                    .into_owned();
+                stats.insert(name, unsafe { named_value(kn) });
+            }
+
+            Some(stats)
+        }
+    }
+
+    /// Decode a `KstatNamed` entry according to its `data_type` byte.
+    unsafe fn named_value(kn: &KstatNamed) -> super::KstatNamedValue {
+        match kn.data_type {
+            KSTAT_DATA_CHAR => super::KstatNamedValue::String(data_to_string(&kn.value.c)),
+            KSTAT_DATA_INT32 => super::KstatNamedValue::Int32(kn.value.i32),
+            KSTAT_DATA_UINT32 => super::KstatNamedValue::U32(kn.value.ui32 as u64),
+            KSTAT_DATA_INT64 => super::KstatNamedValue::Int64(kn.value.i64),
+            KSTAT_DATA_UINT64 => super::KstatNamedValue::UInt64(kn.value.ui64),
+            KSTAT_DATA_LONG => super::KstatNamedValue::Int64(kn.value.l),
+            KSTAT_DATA_ULONG => super::KstatNamedValue::UInt64(kn.value.ul),
+            // Anything else is unrecognized; read it as a string rather
+            // than risk misinterpreting the union.
+            _ => super::KstatNamedValue::String(data_to_string(&kn.value.c)),
+        }
+    }
+
+    fn data_to_string(c: &[c_char; 16]) -> String {
+        let bytes: Vec<u8> = c
+            .iter()
+            .take_while(|&&b| b != 0)
+            .map(|&b| b as u8)
+            .collect();
+        String::from_utf8_lossy(&bytes).into_owned()
     }
 
     impl Drop for KstatWrapper {
@@ -237,6 +387,23 @@ pub(crate) fn ncpus() -> Result<usize> {
     Err(KstatError::Error("cpu count kstat not found".into()))
 }
 
+pub(crate) fn boot_time() -> Result<i64> {
+    let mut k = wrapper::KstatWrapper::open()?;
+
+    k.lookup(Some(c(MODULE_UNIX)), Some(c(NAME_SYSTEM_MISC)));
+    while k.step() {
+        if k.module() != c(MODULE_UNIX) || k.name() != c(NAME_SYSTEM_MISC) {
+            continue;
+        }
+
+        if let Some(boot_time) = k.data_i64(c(STAT_BOOT_TIME)) {
+            return Ok(boot_time);
+        }
+    }
+
+    Err(KstatError::Error("boot time kstat not found".into()))
+}
+
 pub(crate) fn zone_cpu_cap() -> Result<Option<u64>> {
     let mut k = wrapper::KstatWrapper::open()?;
     let zoneid = zonename::getzoneid().map_err(KstatError::Zoneid)?;
@@ -255,3 +422,140 @@ pub(crate) fn zone_cpu_cap() -> Result<Option<u64>> {
 
     Ok(None)
 }
+
+/// Snapshot of a zone's memory accounting, mirroring the fields illumos
+/// tracks per `memory_cap` rctl: a physical memory cap and current resident
+/// set size, plus the equivalent pair for swap.
+///
+/// When no per-zone `memory_cap` kstat exists (e.g. the global zone, or a
+/// zone with no cap configured), `physcap`/`swapcap` are `None` and
+/// `rss`/`swap` are `0` — there is no per-zone usage to report, so these
+/// fields never stand in for a system-wide figure. Callers that want a
+/// physical-memory figure even in that case should use the crate-level
+/// `zone_memory()`, which falls back to total system memory.
+pub struct MemoryCap {
+    pub physcap: Option<u64>,
+    pub rss: u64,
+    pub swap: u64,
+    pub swapcap: Option<u64>,
+}
+
+pub(crate) fn zone_memory_cap() -> Result<MemoryCap> {
+    let mut k = wrapper::KstatWrapper::open()?;
+    let zoneid = zonename::getzoneid().map_err(KstatError::Zoneid)?;
+    let name = std::ffi::CString::new(format!("{}", zoneid)).expect("invalid CString");
+
+    k.lookup(Some(c(MODULE_MEMORY_CAP)), Some(&name));
+    while k.step() {
+        if k.module() != c(MODULE_MEMORY_CAP) || k.name() != name.as_c_str() {
+            continue;
+        }
+
+        return Ok(MemoryCap {
+            physcap: k.data_ulong(c(STAT_PHYSCAP)).and_then(normalize_cap),
+            rss: k.data_ulong(c(STAT_RSS)).unwrap_or(0),
+            swap: k.data_ulong(c(STAT_SWAP)).unwrap_or(0),
+            swapcap: k.data_ulong(c(STAT_SWAPCAP)).and_then(normalize_cap),
+        });
+    }
+
+    // No per-zone memory_cap kstat (e.g. the global zone, or a zone with no
+    // cap configured): there is no zone-specific usage to report, so leave
+    // rss/swap unset rather than mislabel system-wide usage as this zone's.
+    Ok(MemoryCap {
+        physcap: None,
+        rss: 0,
+        swap: 0,
+        swapcap: None,
+    })
+}
+
+/// Read `unix:0:system_pages` and return `(physmem, freemem)` in bytes.
+fn system_pages() -> Result<(u64, u64)> {
+    let mut k = wrapper::KstatWrapper::open()?;
+
+    k.lookup(Some(c(MODULE_UNIX)), Some(c(NAME_SYSTEM_PAGES)));
+    while k.step() {
+        if k.module() != c(MODULE_UNIX) || k.name() != c(NAME_SYSTEM_PAGES) {
+            continue;
+        }
+
+        let page_size = page_size();
+        let physmem = k.data_ulong(c(STAT_PHYSMEM)).unwrap_or(0) * page_size;
+        let freemem = k.data_ulong(c(STAT_FREEMEM)).unwrap_or(0) * page_size;
+
+        return Ok((physmem, freemem));
+    }
+
+    Err(KstatError::Error("system page count kstat not found".into()))
+}
+
+pub(crate) fn physmem_total() -> Result<u64> {
+    system_pages().map(|(physmem, _)| physmem)
+}
+
+/// Per-CPU clock speed and online state, as reported by the `cpu_info`
+/// kstat module (one instance per online CPU).
+pub struct CpuInfo {
+    pub instance: i32,
+    pub clock_mhz: u64,
+    pub state: String,
+}
+
+pub(crate) fn cpu_clock_mhz() -> Result<Vec<CpuInfo>> {
+    let mut k = wrapper::KstatWrapper::open()?;
+    let mut cpus = Vec::new();
+
+    k.lookup(Some(c(MODULE_CPU_INFO)), None);
+    while k.step() {
+        if k.module() != c(MODULE_CPU_INFO) {
+            continue;
+        }
+
+        if let Some(clock_mhz) = k.data_u32(c(STAT_CLOCK_MHZ)) {
+            cpus.push(CpuInfo {
+                instance: k.instance(),
+                clock_mhz,
+                state: k.data_string(c(STAT_STATE)).unwrap_or_default(),
+            });
+        }
+    }
+
+    Ok(cpus)
+}
+
+/// Snapshot every instance of a named kstat, decoding all of its statistics
+/// instead of requiring a hardcoded lookup per field.  `name` of `None`
+/// matches every instance of `module`, mirroring `KstatWrapper::lookup`.
+pub(crate) fn collect(
+    module: &str,
+    name: Option<&str>,
+) -> Result<Vec<(i32, HashMap<String, KstatNamedValue>)>> {
+    let module = std::ffi::CString::new(module)
+        .map_err(|_| KstatError::Error("invalid module name".into()))?;
+    let name = name
+        .map(std::ffi::CString::new)
+        .transpose()
+        .map_err(|_| KstatError::Error("invalid kstat name".into()))?;
+
+    let mut k = wrapper::KstatWrapper::open()?;
+    k.lookup(Some(module.as_c_str()), name.as_deref());
+
+    let mut results = Vec::new();
+    while k.step() {
+        if k.module() != module.as_c_str() {
+            continue;
+        }
+        if let Some(name) = &name {
+            if k.name() != name.as_c_str() {
+                continue;
+            }
+        }
+
+        if let Some(stats) = k.snapshot() {
+            results.push((k.instance(), stats));
+        }
+    }
+
+    Ok(results)
+}
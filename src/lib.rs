@@ -1,7 +1,11 @@
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use thiserror::Error;
 
 mod kstat;
 
+pub use kstat::{CpuInfo, KstatNamedValue, MemoryCap};
+
 #[derive(Error, Debug)]
 pub enum ZoneInfoError {
     #[error("kstat lookup failed")]
@@ -20,6 +24,55 @@ pub fn zone_cpus() -> Result<usize> {
     kstat::ncpus().map_err(ZoneInfoError::from)
 }
 
+/// The host's boot time, in seconds since the Unix epoch.
+pub fn boot_time() -> Result<i64> {
+    kstat::boot_time().map_err(ZoneInfoError::from)
+}
+
+/// How long the host (and therefore the zone) has been up.
+pub fn uptime() -> Result<Duration> {
+    let boot_time = boot_time()?;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|_| {
+            ZoneInfoError::Kstat(kstat::KstatError::Error(
+                "system clock before Unix epoch".into(),
+            ))
+        })?
+        .as_secs() as i64;
+
+    Ok(Duration::from_secs((now - boot_time).max(0) as u64))
+}
+
+pub fn zone_memory() -> Result<u64> {
+    let cap = kstat::zone_memory_cap()?;
+    if let Some(physcap) = cap.physcap {
+        return Ok(physcap);
+    }
+
+    kstat::physmem_total().map_err(ZoneInfoError::from)
+}
+
+pub fn zone_memory_cap() -> Result<MemoryCap> {
+    kstat::zone_memory_cap().map_err(ZoneInfoError::from)
+}
+
+pub fn cpu_clock_mhz() -> Result<Vec<CpuInfo>> {
+    kstat::cpu_clock_mhz().map_err(ZoneInfoError::from)
+}
+
+/// Snapshot every instance of a named kstat, e.g. `collect("caps", None)`
+/// for all per-zone CPU caps, `collect("memory_cap", None)` for all
+/// per-zone memory caps, or `collect("zone_vfs", Some("zone_42"))` for one
+/// zone's VFS I/O stats.  Useful for reading fields this crate doesn't
+/// expose a dedicated accessor for.
+pub fn collect(
+    module: &str,
+    name: Option<&str>,
+) -> Result<Vec<(i32, HashMap<String, KstatNamedValue>)>> {
+    kstat::collect(module, name).map_err(ZoneInfoError::from)
+}
+
 pub fn zoneid() -> Result<i32> {
     Ok(zonename::getzoneid()?)
 }